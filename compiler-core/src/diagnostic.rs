@@ -0,0 +1,187 @@
+use crate::ast::SrcSpan;
+use codespan_reporting::{
+    diagnostic::{self as codespan, Label as CodespanLabel},
+    files::SimpleFile,
+    term::{
+        self,
+        termcolor::{Buffer, Color, ColorSpec, WriteColor},
+        Chars, Config, DisplayStyle, Styles,
+    },
+};
+use ecow::EcoString;
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+impl Level {
+    fn severity(&self) -> codespan::Severity {
+        match self {
+            Level::Error => codespan::Severity::Error,
+            Level::Warning => codespan::Severity::Warning,
+        }
+    }
+}
+
+/// A label pointing at a span of source code, with an optional message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub text: Option<String>,
+    pub span: SrcSpan,
+}
+
+impl Label {
+    fn to_codespan(&self, style: codespan::LabelStyle) -> CodespanLabel<()> {
+        let label = CodespanLabel::new(style, (), self.span.start as usize..self.span.end as usize);
+        match &self.text {
+            Some(text) => label.with_message(text.clone()),
+            None => label,
+        }
+    }
+}
+
+/// The source location a diagnostic refers to: the primary label along with any
+/// additional labels that point at related spans in the same file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub src: EcoString,
+    pub label: Label,
+    pub extra_labels: Vec<Label>,
+}
+
+/// A secondary location that explains a primary diagnostic, mirroring rustc's
+/// subdiagnostics. Each child carries its own path, span and a snapshot of the
+/// source it was computed against (mirroring `Location.src`, so the reported
+/// range always matches the buffer the span was resolved from even if the
+/// file on disk has since changed) along with a short message (e.g. "this is
+/// the conflicting definition here" or "consider importing this instead") and
+/// maps to an LSP `DiagnosticRelatedInformation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelatedDiagnostic {
+    pub path: PathBuf,
+    pub src: EcoString,
+    pub span: SrcSpan,
+    pub message: String,
+}
+
+/// How confident the compiler is that a suggested fix is correct, mirroring
+/// rustc/rustfix's applicability levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Applicability {
+    /// The suggestion is definitely correct and can be applied automatically
+    /// without human review.
+    MachineApplicable,
+    /// The suggestion may be correct but could still require human review.
+    MaybeIncorrect,
+    /// The suggestion contains placeholders that a human must fill in.
+    HasPlaceholders,
+    /// The confidence of the suggestion is not known.
+    Unspecified,
+}
+
+/// A machine-readable suggestion attached to a diagnostic. Applying it replaces
+/// each source span with the corresponding replacement text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub message: String,
+    pub substitutions: Vec<(SrcSpan, String)>,
+    pub applicability: Applicability,
+}
+
+/// A diagnostic is a message to the user about their program. It may be an
+/// error that stopped compilation or a warning about something suspicious.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub title: String,
+    pub text: String,
+    pub level: Level,
+    pub location: Option<Location>,
+    pub hint: Option<String>,
+    /// A stable machine-readable identifier for the category of this
+    /// diagnostic (akin to a rustc lint name, e.g. `"unused-import"`). Unlike
+    /// the human-readable `title` it does not change when the wording is
+    /// reworded, so it is a safe key for severity policies to configure
+    /// against. `None` for diagnostics that belong to no configurable category.
+    ///
+    /// No `Warning`/`Error` producer sets this yet, so `SeverityPolicy`'s
+    /// per-category overrides have nothing to match against today; populating
+    /// it on the real producers is a follow-up.
+    pub category: Option<EcoString>,
+    /// Machine-readable suggested fixes for this diagnostic. The language
+    /// server turns `MachineApplicable` suggestions into quick fixes and
+    /// `gleam <command> --fix` applies them to disk.
+    pub suggestions: Vec<Suggestion>,
+    /// Secondary locations that explain this diagnostic. The language server
+    /// surfaces them as LSP `DiagnosticRelatedInformation`.
+    pub children: Vec<RelatedDiagnostic>,
+}
+
+impl Default for Diagnostic {
+    fn default() -> Self {
+        Self {
+            title: String::new(),
+            text: String::new(),
+            level: Level::Error,
+            location: None,
+            hint: None,
+            category: None,
+            suggestions: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn write(&self, buffer: &mut Buffer) {
+        match &self.location {
+            Some(location) => self.write_span(location, buffer),
+            None => self.write_title(buffer),
+        }
+    }
+
+    fn write_title(&self, buffer: &mut Buffer) {
+        let _ = buffer.set_color(ColorSpec::new().set_bold(true).set_fg(Some(color(self.level))));
+        let _ = write!(buffer, "{}", self.title);
+        let _ = buffer.set_color(&ColorSpec::new());
+        if !self.text.is_empty() {
+            let _ = write!(buffer, "\n{}", self.text);
+        }
+    }
+
+    fn write_span(&self, location: &Location, buffer: &mut Buffer) {
+        let mut labels = vec![location.label.to_codespan(codespan::LabelStyle::Primary)];
+        for label in &location.extra_labels {
+            labels.push(label.to_codespan(codespan::LabelStyle::Secondary));
+        }
+
+        let diagnostic = codespan::Diagnostic::new(self.level.severity())
+            .with_message(self.title.clone())
+            .with_labels(labels)
+            .with_notes(if self.text.is_empty() {
+                vec![]
+            } else {
+                vec![self.text.clone()]
+            });
+
+        let file = SimpleFile::new(location.path.to_string_lossy().to_string(), &location.src);
+        let config = Config {
+            display_style: DisplayStyle::Rich,
+            chars: Chars::ascii(),
+            styles: Styles::default(),
+            ..Default::default()
+        };
+        let _ = term::emit(buffer, &config, &file, &diagnostic);
+    }
+}
+
+fn color(level: Level) -> Color {
+    match level {
+        Level::Error => Color::Red,
+        Level::Warning => Color::Yellow,
+    }
+}
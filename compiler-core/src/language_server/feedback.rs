@@ -1,13 +1,270 @@
-use crate::{diagnostic::Diagnostic, Error, Warning};
+use crate::{
+    ast::SrcSpan,
+    diagnostic::{Applicability, Diagnostic, Level},
+    Error, Warning,
+};
+use ecow::EcoString;
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, DiagnosticRelatedInformation,
+    Location as LspLocation, Position, Range, TextEdit, Url, WorkspaceEdit,
+};
+use serde::Serialize;
 use std::{
     collections::{HashMap, HashSet},
+    hash::{Hash, Hasher},
     path::PathBuf,
 };
 
-#[derive(Debug, Default, PartialEq, Eq)]
+/// A span resolved to both byte offsets and 1-based line/column positions,
+/// ready to be serialised for external tooling.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct JsonSpan {
+    pub byte_start: u32,
+    pub byte_end: u32,
+    pub line_start: usize,
+    pub column_start: usize,
+    pub line_end: usize,
+    pub column_end: usize,
+}
+
+/// A single diagnostic rendered as flat JSON, modelled on rustc's JSON emitter.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct JsonDiagnostic {
+    pub level: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+    pub spans: Vec<JsonSpan>,
+}
+
+/// A whole `Feedback` rendered as a flat JSON document. Editors and CI that do
+/// not speak LSP can consume this via `gleam build --error-format=json`.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct JsonFeedback {
+    pub diagnostics: HashMap<String, Vec<JsonDiagnostic>>,
+    pub messages: Vec<JsonDiagnostic>,
+}
+
+/// Resolve a byte offset into a source string to a 1-based line and column.
+fn line_and_column(src: &str, offset: u32) -> (usize, usize) {
+    let offset = offset as usize;
+    let mut line = 1;
+    let mut column = 1;
+    for (index, character) in src.char_indices() {
+        if index >= offset {
+            break;
+        }
+        if character == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
+}
+
+/// The JSON level name for a diagnostic, following rustc's vocabulary. `note`
+/// is unused as nothing in this compiler produces a diagnostic of that level;
+/// `help` is never returned from here as it is synthesised separately for a
+/// diagnostic's hint, see `diagnostic_to_json`.
+fn level_name(level: &Level) -> &'static str {
+    match level {
+        Level::Error => "error",
+        Level::Warning => "warning",
+    }
+}
+
+/// Render a single diagnostic as its flat JSON representation(s), resolving
+/// each span to line/column positions using the diagnostic's own source. A
+/// diagnostic with a hint is rendered as two entries: the primary
+/// `error`/`warning` entry followed by a `help` entry carrying the hint text,
+/// mirroring rustc's JSON emitter so tooling does not silently lose the hints
+/// real Gleam diagnostics rely on to explain a fix.
+fn diagnostic_to_json(diagnostic: &Diagnostic) -> Vec<JsonDiagnostic> {
+    let mut spans = Vec::new();
+    let mut snippet = None;
+
+    if let Some(location) = diagnostic.location.as_ref() {
+        let src = location.src.as_str();
+        let labels = std::iter::once(&location.label).chain(location.extra_labels.iter());
+        for label in labels {
+            let span = label.span;
+            let (line_start, column_start) = line_and_column(src, span.start);
+            let (line_end, column_end) = line_and_column(src, span.end);
+            spans.push(JsonSpan {
+                byte_start: span.start,
+                byte_end: span.end,
+                line_start,
+                column_start,
+                line_end,
+                column_end,
+            });
+        }
+        snippet = src
+            .get(location.label.span.start as usize..location.label.span.end as usize)
+            .map(|snippet| snippet.to_string());
+    }
+
+    let mut rendered = vec![JsonDiagnostic {
+        level: level_name(&diagnostic.level),
+        message: diagnostic.title.clone(),
+        snippet,
+        spans,
+    }];
+
+    if let Some(hint) = diagnostic.hint.as_ref() {
+        rendered.push(JsonDiagnostic {
+            level: "help",
+            message: hint.clone(),
+            snippet: None,
+            spans: Vec::new(),
+        });
+    }
+
+    rendered
+}
+
+/// Apply a set of source substitutions to a file's source, returning the
+/// edited source.
+///
+/// Substitutions are applied from the end of the file backwards so that the
+/// byte offsets of earlier edits remain valid as we go, and any substitution
+/// whose span overlaps one that has already been applied is skipped so that
+/// edits can never corrupt each other.
+///
+fn apply_substitutions(src: &str, mut substitutions: Vec<(SrcSpan, String)>) -> String {
+    // Sort by start offset so we can detect overlaps and apply in order.
+    substitutions.sort_by_key(|(span, _)| span.start);
+
+    let mut applied: Vec<(SrcSpan, String)> = Vec::with_capacity(substitutions.len());
+    let mut last_end = 0;
+    for (span, replacement) in substitutions {
+        // Skip any substitution that overlaps one we have already accepted.
+        if span.start < last_end {
+            continue;
+        }
+        last_end = span.end;
+        applied.push((span, replacement));
+    }
+
+    // Apply from the end of the file backwards so earlier byte offsets stay
+    // valid after each edit.
+    let mut edited = src.to_string();
+    for (span, replacement) in applied.into_iter().rev() {
+        edited.replace_range(span.start as usize..span.end as usize, &replacement);
+    }
+    edited
+}
+
+/// Resolve a byte offset into an LSP zero-based position.
+fn position(src: &str, offset: u32) -> Position {
+    let (line, column) = line_and_column(src, offset);
+    Position {
+        line: (line - 1) as u32,
+        character: (column - 1) as u32,
+    }
+}
+
+/// Resolve a source span into an LSP range.
+fn range(src: &str, span: SrcSpan) -> Range {
+    Range {
+        start: position(src, span.start),
+        end: position(src, span.end),
+    }
+}
+
+/// Translate the related child diagnostics into LSP `DiagnosticRelatedInformation`
+/// so users can jump between the primary diagnostic location and the secondary
+/// locations that explain it. Each child's span is resolved against the source
+/// snapshot it was captured against, the same as the primary `Location.src`,
+/// rather than the file on disk, which may have drifted from the buffer the
+/// diagnostic's spans were computed against.
+pub fn related_information(diagnostic: &Diagnostic) -> Vec<DiagnosticRelatedInformation> {
+    diagnostic
+        .children
+        .iter()
+        .filter_map(|child| {
+            let uri = Url::from_file_path(&child.path).ok()?;
+            Some(DiagnosticRelatedInformation {
+                location: LspLocation {
+                    uri,
+                    range: range(child.src.as_str(), child.span),
+                },
+                message: child.message.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Translate the machine-applicable suggestions of a diagnostic into LSP code
+/// actions (quick fixes) for the given document. Suggestions that are not
+/// `MachineApplicable` are left for the user to apply by hand and so are not
+/// offered as automatic fixes.
+pub fn code_actions(uri: &Url, diagnostic: &Diagnostic) -> Vec<CodeActionOrCommand> {
+    let location = match diagnostic.location.as_ref() {
+        Some(location) => location,
+        None => return vec![],
+    };
+    let src = location.src.as_str();
+
+    diagnostic
+        .suggestions
+        .iter()
+        .filter(|suggestion| suggestion.applicability == Applicability::MachineApplicable)
+        .map(|suggestion| {
+            let edits = suggestion
+                .substitutions
+                .iter()
+                .map(|(span, new_text)| TextEdit {
+                    range: range(src, *span),
+                    new_text: new_text.clone(),
+                })
+                .collect();
+            let mut changes = HashMap::new();
+            _ = changes.insert(uri.clone(), edits);
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: suggestion.message.clone(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(changes),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Default)]
 pub struct Feedback {
     pub diagnostics: HashMap<PathBuf, Vec<Diagnostic>>,
     pub messages: Vec<Diagnostic>,
+    /// Stable hashes of the diagnostics already emitted for each file, used to
+    /// collapse repeated identical diagnostics to a single squiggle. Not part
+    /// of the feedback's observable value, so it is excluded from equality.
+    emitted_hashes: HashMap<PathBuf, HashSet<u64>>,
+}
+
+impl PartialEq for Feedback {
+    fn eq(&self, other: &Self) -> bool {
+        self.diagnostics == other.diagnostics && self.messages == other.messages
+    }
+}
+
+impl Eq for Feedback {}
+
+/// A stable hash of a diagnostic based on its location span and message text,
+/// used to detect diagnostics that are identical for deduplication purposes.
+fn diagnostic_hash(diagnostic: &Diagnostic) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    diagnostic.title.hash(&mut hasher);
+    diagnostic.text.hash(&mut hasher);
+    if let Some(location) = diagnostic.location.as_ref() {
+        location.label.span.start.hash(&mut hasher);
+        location.label.span.end.hash(&mut hasher);
+    }
+    hasher.finish()
 }
 
 impl Feedback {
@@ -17,16 +274,179 @@ impl Feedback {
         _ = self.diagnostics.insert(path, vec![]);
     }
 
-    pub fn append_diagnostic(&mut self, path: PathBuf, diagnostic: Diagnostic) {
+    /// Overwrite the diagnostics for a file with the given vector. Used to
+    /// re-send still-valid warnings for a file whose error has been resolved.
+    pub fn set_existing_diagnostics(&mut self, path: PathBuf, diagnostics: Vec<Diagnostic>) {
+        _ = self.diagnostics.insert(path, diagnostics);
+    }
+
+    /// Append a diagnostic for a file, collapsing it if it is identical to one
+    /// already emitted for that file so clients don't see duplicate squiggles
+    /// when the compiler reports the same issue from multiple code paths. The
+    /// diagnostic is stored whole, so any related child locations are
+    /// preserved alongside it for the language server to surface.
+    ///
+    /// Returns whether the diagnostic was actually added, so callers that
+    /// cache diagnostics elsewhere (e.g. to re-send them later) can skip
+    /// caching a duplicate that was dropped here.
+    pub fn append_diagnostic(&mut self, path: PathBuf, diagnostic: Diagnostic) -> bool {
+        let is_new = self
+            .emitted_hashes
+            .entry(path.clone())
+            .or_default()
+            .insert(diagnostic_hash(&diagnostic));
+        if !is_new {
+            return false;
+        }
         self.diagnostics
             .entry(path)
             .or_insert_with(Vec::new)
             .push(diagnostic);
+        true
     }
 
     fn append_message(&mut self, diagnostic: Diagnostic) {
         self.messages.push(diagnostic);
     }
+
+    /// Collect the substitutions of every machine-applicable suggestion in this
+    /// feedback, grouped by the file they apply to so that all edits for a file
+    /// can be applied together. Each file's substitutions are paired with the
+    /// source snapshot they were computed against (the same `location.src`
+    /// used to render the diagnostic), so callers apply them against the
+    /// buffer the spans actually refer to rather than whatever is on disk by
+    /// the time the fix is applied. A diagnostic with no location has nowhere
+    /// to apply its suggestions and is skipped.
+    pub fn machine_applicable_substitutions(
+        &self,
+    ) -> HashMap<PathBuf, (EcoString, Vec<(SrcSpan, String)>)> {
+        let mut substitutions: HashMap<PathBuf, (EcoString, Vec<(SrcSpan, String)>)> =
+            HashMap::new();
+        for (path, diagnostics) in &self.diagnostics {
+            for diagnostic in diagnostics {
+                let location = match diagnostic.location.as_ref() {
+                    Some(location) => location,
+                    None => continue,
+                };
+                for suggestion in &diagnostic.suggestions {
+                    if suggestion.applicability == Applicability::MachineApplicable {
+                        substitutions
+                            .entry(path.clone())
+                            .or_insert_with(|| (location.src.clone(), Vec::new()))
+                            .1
+                            .extend(suggestion.substitutions.iter().cloned());
+                    }
+                }
+            }
+        }
+        substitutions
+    }
+
+    /// Render this feedback as a flat JSON document, modelled on rustc's JSON
+    /// emitter, so tooling that does not speak LSP can still consume
+    /// diagnostics. Diagnostics are keyed per file and locationless messages
+    /// are kept separately.
+    pub fn to_json_feedback(&self) -> JsonFeedback {
+        let diagnostics = self
+            .diagnostics
+            .iter()
+            .map(|(path, diagnostics)| {
+                let path = path.to_string_lossy().to_string();
+                let diagnostics = diagnostics.iter().flat_map(diagnostic_to_json).collect();
+                (path, diagnostics)
+            })
+            .collect();
+        let messages = self.messages.iter().flat_map(diagnostic_to_json).collect();
+        JsonFeedback {
+            diagnostics,
+            messages,
+        }
+    }
+
+    /// Serialise this feedback to a JSON string for `--error-format=json`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.to_json_feedback())
+            .expect("JSON feedback serialisation should not fail")
+    }
+
+    /// Apply every machine-applicable suggestion in this feedback to the files
+    /// on disk, returning the paths that were edited. All substitutions for a
+    /// given file are applied together so overlapping edits are resolved
+    /// safely. Substitutions are applied against the source snapshot they
+    /// were computed from, not a fresh read of the file, since the file on
+    /// disk may have drifted from the buffer the spans were resolved against
+    /// (the same hazard `related_information` has to guard against). This
+    /// backs `gleam <command> --fix`.
+    pub fn apply_machine_applicable_fixes(&self) -> std::io::Result<Vec<PathBuf>> {
+        let mut edited = Vec::new();
+        for (path, (src, substitutions)) in self.machine_applicable_substitutions() {
+            let new_src = apply_substitutions(&src, substitutions);
+            if new_src != src.as_str() {
+                std::fs::write(&path, new_src)?;
+                edited.push(path);
+            }
+        }
+        edited.sort();
+        Ok(edited)
+    }
+}
+
+/// The level at which a warning category is treated, akin to rustc's lint
+/// levels. `Allow` silences the warning, `Warn` reports it as a warning, and
+/// `Deny` promotes it to a build-failing error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeverityLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Default for SeverityLevel {
+    fn default() -> Self {
+        Self::Warn
+    }
+}
+
+/// A configurable policy describing how warnings are treated, with a default
+/// level and per-category overrides. This gives `gleam check --deny-warnings`
+/// and finer per-category allow/warn/deny control without changing the
+/// underlying warning producers.
+///
+/// The per-category overrides are keyed on `Diagnostic.category`, which no
+/// warning or error producer in this tree currently sets (it is always
+/// `None`), so `set_category` has nothing to match against yet and only the
+/// blanket `deny_warnings` policy is reachable end to end. Populating
+/// `category` on the real `Warning`/`Error` variants as they are produced is
+/// a follow-up; `level_for` is written and tested against the category it
+/// will receive once that's done.
+#[derive(Debug, Default)]
+pub struct SeverityPolicy {
+    default: SeverityLevel,
+    categories: HashMap<String, SeverityLevel>,
+}
+
+impl SeverityPolicy {
+    /// A policy that promotes every warning to an error, for `--deny-warnings`.
+    pub fn deny_warnings() -> Self {
+        Self {
+            default: SeverityLevel::Deny,
+            categories: HashMap::new(),
+        }
+    }
+
+    /// Override the level applied to a specific warning category.
+    pub fn set_category(&mut self, category: impl Into<String>, level: SeverityLevel) {
+        _ = self.categories.insert(category.into(), level);
+    }
+
+    /// The level to apply to a warning of the given category, falling back to
+    /// the default level when the warning has no category or the category has
+    /// no specific rule.
+    fn level_for(&self, category: Option<&str>) -> SeverityLevel {
+        category
+            .and_then(|category| self.categories.get(category).copied())
+            .unwrap_or(self.default)
+    }
 }
 
 /// When an operation succeeds or fails we want to send diagnostics and
@@ -44,9 +464,26 @@ impl Feedback {
 pub struct FeedbackBookKeeper {
     files_with_warnings: HashSet<PathBuf>,
     files_with_errors: HashSet<PathBuf>,
+    /// The most recently emitted warning diagnostics for each file. When a
+    /// file's error is cleared because it compiled successfully we re-send
+    /// these cached warnings rather than erasing the file outright, so that
+    /// warnings which are still valid are not clobbered along with the error.
+    cached_warnings: HashMap<PathBuf, Vec<Diagnostic>>,
+    /// Policy controlling whether each warning category is allowed, warned, or
+    /// promoted to a build-failing error.
+    severity: SeverityPolicy,
 }
 
 impl FeedbackBookKeeper {
+    /// Construct a book keeper with a custom severity policy, e.g. one that
+    /// denies warnings.
+    pub fn with_severity_policy(severity: SeverityPolicy) -> Self {
+        Self {
+            severity,
+            ..Self::default()
+        }
+    }
+
     /// Send diagnostics for any warnings and remove any diagnostics for files
     /// that have compiled without warnings.
     ///
@@ -61,6 +498,10 @@ impl FeedbackBookKeeper {
         // longer valid so we set an empty vector of diagnostics for the files
         // to erase their diagnostics.
         for path in compiled {
+            // The file has been recompiled so its previous warnings are no
+            // longer valid. Drop the cache entry; any warnings that still apply
+            // will be re-added from `warnings` below.
+            _ = self.cached_warnings.remove(&path);
             let has_existing_diagnostics = self.files_with_warnings.remove(&path);
             if has_existing_diagnostics {
                 feedback.unset_existing_diagnostics(path);
@@ -71,11 +512,16 @@ impl FeedbackBookKeeper {
         // successfully. We don't limit this to files that have been compiled as
         // a previous cached version could be used instead of a recompile.
         //
-        // TODO: avoid clobbering warnings. They should be preserved rather than
-        // removed with the errors here. We will need to store the warnings and
-        // re-send them.
+        // A file that had an error may also have had warnings that are still
+        // valid. Rather than clobbering them we re-send the cached warnings for
+        // the path, only truly emptying the file if it had none.
         for path in self.files_with_errors.drain() {
-            feedback.unset_existing_diagnostics(path);
+            match self.cached_warnings.get(&path) {
+                Some(warnings) if !warnings.is_empty() => {
+                    feedback.set_existing_diagnostics(path, warnings.clone());
+                }
+                _ => feedback.unset_existing_diagnostics(path),
+            }
         }
 
         for warning in warnings {
@@ -103,7 +549,7 @@ impl FeedbackBookKeeper {
         match diagnostic.location.as_ref().map(|l| l.path.clone()) {
             Some(path) => {
                 _ = self.files_with_errors.insert(path.clone());
-                feedback.append_diagnostic(path, diagnostic);
+                _ = feedback.append_diagnostic(path, diagnostic);
             }
 
             None => {
@@ -119,10 +565,38 @@ impl FeedbackBookKeeper {
     }
 
     fn insert_warning(&mut self, feedback: &mut Feedback, warning: Warning) {
-        let diagnostic = warning.to_diagnostic();
-        if let Some(path) = diagnostic.location.as_ref().map(|l| l.path.clone()) {
-            _ = self.files_with_warnings.insert(path.clone());
-            feedback.append_diagnostic(path, diagnostic);
+        let mut diagnostic = warning.to_diagnostic();
+        let path = match diagnostic.location.as_ref().map(|l| l.path.clone()) {
+            Some(path) => path,
+            None => return,
+        };
+
+        match self.severity.level_for(diagnostic.category.as_deref()) {
+            // The category is silenced, so the diagnostic is dropped entirely.
+            SeverityLevel::Allow => {}
+
+            // A regular warning. Track the file as having warnings and, if it
+            // was not a duplicate of one already emitted this compile, cache
+            // the diagnostic so it can be re-sent if a later error is cleared.
+            SeverityLevel::Warn => {
+                _ = self.files_with_warnings.insert(path.clone());
+                let added = feedback.append_diagnostic(path.clone(), diagnostic.clone());
+                if added {
+                    self.cached_warnings
+                        .entry(path)
+                        .or_insert_with(Vec::new)
+                        .push(diagnostic);
+                }
+            }
+
+            // The category is denied, so the warning is promoted to an error
+            // and tracked in `files_with_errors` so it fails the build and is
+            // cleared with the same lifecycle as a real error.
+            SeverityLevel::Deny => {
+                diagnostic.level = Level::Error;
+                _ = self.files_with_errors.insert(path.clone());
+                _ = feedback.append_diagnostic(path, diagnostic);
+            }
         }
     }
 }
@@ -132,6 +606,7 @@ mod tests {
     use super::*;
     use crate::{
         ast::SrcSpan,
+        diagnostic::{Label, Location, RelatedDiagnostic, Suggestion},
         parse::error::{ParseError, ParseErrorType},
         type_,
     };
@@ -166,13 +641,12 @@ mod tests {
         assert_eq!(
             Feedback {
                 diagnostics: HashMap::from([
-                    (
-                        file1.clone(),
-                        vec![warning1.to_diagnostic(), warning1.to_diagnostic(),]
-                    ),
+                    // The duplicate warning1 is collapsed to a single diagnostic.
+                    (file1.clone(), vec![warning1.to_diagnostic()]),
                     (file2.clone(), vec![warning2.to_diagnostic(),])
                 ]),
                 messages: vec![],
+                ..Default::default()
             },
             feedback
         );
@@ -191,6 +665,7 @@ mod tests {
                     // File 3 had no diagnostics so does not need to to be unset
                 ]),
                 messages: vec![],
+                ..Default::default()
             },
             feedback
         );
@@ -224,6 +699,7 @@ mod tests {
             Feedback {
                 diagnostics: HashMap::from([(file1, vec![warning1.to_diagnostic()])]),
                 messages: vec![locationless_error.to_diagnostic()],
+                ..Default::default()
             },
             feedback
         );
@@ -264,6 +740,7 @@ mod tests {
                     (file3.clone(), vec![error.to_diagnostic()]),
                 ]),
                 messages: vec![],
+                ..Default::default()
             },
             feedback
         );
@@ -276,11 +753,220 @@ mod tests {
             Feedback {
                 diagnostics: HashMap::from([(file3, vec![])]),
                 messages: vec![],
+                ..Default::default()
+            },
+            feedback
+        );
+    }
+
+    #[test]
+    fn warnings_are_preserved_when_an_error_is_cleared() {
+        // A file can have both a warning and an error. When the error is
+        // resolved by a successful compilation the still-valid warning must be
+        // re-sent rather than erased along with the error.
+
+        let mut book_keeper = FeedbackBookKeeper::default();
+        let file = PathBuf::from("src/file1.gleam");
+
+        let warning = Warning::Type {
+            path: file.clone(),
+            src: "src".into(),
+            warning: type_::Warning::NoFieldsRecordUpdate {
+                location: SrcSpan::new(1, 2),
+            },
+        };
+        let error = Error::Parse {
+            path: file.clone(),
+            src: "blah".into(),
+            error: ParseError {
+                error: ParseErrorType::ConcatPatternVariableLeftHandSide,
+                location: SrcSpan::new(1, 4),
+            },
+        };
+
+        _ = book_keeper.build_with_error(error, vec![].into_iter(), vec![warning.clone()]);
+
+        // The error compiles away but the file is not recompiled, so its
+        // warning is still valid and should reappear rather than be cleared.
+        let feedback = book_keeper.compiled(vec![].into_iter(), vec![]);
+
+        assert_eq!(
+            Feedback {
+                diagnostics: HashMap::from([(file, vec![warning.to_diagnostic()])]),
+                messages: vec![],
+                ..Default::default()
+            },
+            feedback
+        );
+    }
+
+    #[test]
+    fn duplicate_cached_warning_is_not_resent_twice_when_an_error_is_cleared() {
+        // A file can have the same warning reported twice alongside an error
+        // in the same compile. The duplicate should be collapsed in the
+        // feedback sent to the client, and it must also not be double-cached,
+        // or it would be resent as a duplicate later when the error clears.
+
+        let mut book_keeper = FeedbackBookKeeper::default();
+        let file = PathBuf::from("src/file1.gleam");
+
+        let warning = Warning::Type {
+            path: file.clone(),
+            src: "src".into(),
+            warning: type_::Warning::NoFieldsRecordUpdate {
+                location: SrcSpan::new(1, 2),
+            },
+        };
+        let error = Error::Parse {
+            path: file.clone(),
+            src: "blah".into(),
+            error: ParseError {
+                error: ParseErrorType::ConcatPatternVariableLeftHandSide,
+                location: SrcSpan::new(1, 4),
+            },
+        };
+
+        _ = book_keeper.build_with_error(
+            error,
+            vec![].into_iter(),
+            vec![warning.clone(), warning.clone()],
+        );
+
+        // The error compiles away but the file is not recompiled, so the
+        // cached warning is re-sent. It must still appear only once.
+        let feedback = book_keeper.compiled(vec![].into_iter(), vec![]);
+
+        assert_eq!(
+            Feedback {
+                diagnostics: HashMap::from([(file, vec![warning.to_diagnostic()])]),
+                messages: vec![],
+                ..Default::default()
+            },
+            feedback
+        );
+    }
+
+    #[test]
+    fn deny_warnings_promotes_warnings_to_errors() {
+        let mut book_keeper =
+            FeedbackBookKeeper::with_severity_policy(SeverityPolicy::deny_warnings());
+        let file = PathBuf::from("src/file1.gleam");
+
+        let warning = Warning::Type {
+            path: file.clone(),
+            src: "src".into(),
+            warning: type_::Warning::NoFieldsRecordUpdate {
+                location: SrcSpan::new(1, 2),
+            },
+        };
+
+        let feedback = book_keeper.compiled(vec![].into_iter(), vec![warning]);
+
+        // The warning is emitted for the file as a build-failing diagnostic.
+        let diagnostics = feedback.diagnostics.get(&file).expect("diagnostics");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].level, Level::Error);
+
+        // Because it was tracked as an error, a later clean compilation clears
+        // it even though the file is not recompiled.
+        let feedback = book_keeper.compiled(vec![].into_iter(), vec![]);
+        assert_eq!(
+            Feedback {
+                diagnostics: HashMap::from([(file, vec![])]),
+                messages: vec![],
+                ..Default::default()
+            },
+            feedback
+        );
+    }
+
+    #[test]
+    fn severity_policy_category_override_takes_priority_over_the_default_level() {
+        // No current warning/error producer sets `Diagnostic.category`, but
+        // the policy mechanism itself should still behave correctly once one
+        // does: a category-specific override wins over the default level, and
+        // an uncategorised (or unconfigured-category) warning falls back to
+        // the default.
+        let mut policy = SeverityPolicy::default();
+        policy.set_category("unused-import", SeverityLevel::Allow);
+        policy.set_category("todo", SeverityLevel::Deny);
+
+        assert_eq!(
+            policy.level_for(Some("unused-import")),
+            SeverityLevel::Allow
+        );
+        assert_eq!(policy.level_for(Some("todo")), SeverityLevel::Deny);
+        assert_eq!(
+            policy.level_for(Some("no-override-for-this-one")),
+            SeverityLevel::Warn
+        );
+        assert_eq!(policy.level_for(None), SeverityLevel::Warn);
+    }
+
+    #[test]
+    fn identical_diagnostics_are_deduplicated() {
+        let file = PathBuf::from("src/file1.gleam");
+        let warning = Warning::Type {
+            path: file.clone(),
+            src: "src".into(),
+            warning: type_::Warning::NoFieldsRecordUpdate {
+                location: SrcSpan::new(1, 2),
+            },
+        };
+
+        let mut feedback = Feedback::default();
+        _ = feedback.append_diagnostic(file.clone(), warning.to_diagnostic());
+        _ = feedback.append_diagnostic(file.clone(), warning.to_diagnostic());
+
+        assert_eq!(
+            Feedback {
+                diagnostics: HashMap::from([(file, vec![warning.to_diagnostic()])]),
+                messages: vec![],
+                ..Default::default()
             },
             feedback
         );
     }
 
+    #[test]
+    fn line_and_column_resolves_byte_offsets() {
+        let src = "one\ntwo\nthree\n";
+        assert_eq!(line_and_column(src, 0), (1, 1));
+        assert_eq!(line_and_column(src, 2), (1, 3));
+        assert_eq!(line_and_column(src, 4), (2, 1));
+        assert_eq!(line_and_column(src, 8), (3, 1));
+    }
+
+    #[test]
+    fn apply_substitutions_edits_from_the_end_backwards() {
+        // Multiple non-overlapping substitutions are all applied, and applying
+        // them from the end backwards keeps the earlier offsets valid.
+        let src = "let wibble = 1\nlet wobble = 2\n";
+        let edited = apply_substitutions(
+            src,
+            vec![
+                (SrcSpan::new(4, 10), "x".into()),
+                (SrcSpan::new(19, 25), "y".into()),
+            ],
+        );
+        assert_eq!(edited, "let x = 1\nlet y = 2\n");
+    }
+
+    #[test]
+    fn apply_substitutions_skips_overlapping_spans() {
+        // The second substitution overlaps the first and so is skipped, leaving
+        // the source uncorrupted.
+        let src = "hello world";
+        let edited = apply_substitutions(
+            src,
+            vec![
+                (SrcSpan::new(0, 5), "howdy".into()),
+                (SrcSpan::new(3, 8), "XXXXX".into()),
+            ],
+        );
+        assert_eq!(edited, "howdy world");
+    }
+
     // https://github.com/gleam-lang/gleam/issues/2093
     #[test]
     fn successful_compilation_removes_error_diagnostic() {
@@ -316,6 +1002,7 @@ mod tests {
             Feedback {
                 diagnostics: HashMap::from([(file.clone(), vec![error.to_diagnostic()])]),
                 messages: vec![],
+                ..Default::default()
             },
             feedback
         );
@@ -329,8 +1016,293 @@ mod tests {
             Feedback {
                 diagnostics: HashMap::from([(file, vec![])]),
                 messages: vec![],
+                ..Default::default()
             },
             feedback
         );
     }
+
+    #[test]
+    fn code_actions_builds_a_quick_fix_from_a_machine_applicable_suggestion() {
+        let path = PathBuf::from("/project/src/file1.gleam");
+        let uri = Url::from_file_path(&path).expect("absolute path");
+        let src: EcoString = "let x = 1\n".into();
+        let diagnostic = Diagnostic {
+            location: Some(Location {
+                path: path.clone(),
+                src: src.clone(),
+                label: Label {
+                    text: None,
+                    span: SrcSpan::new(4, 5),
+                },
+                extra_labels: vec![],
+            }),
+            suggestions: vec![
+                Suggestion {
+                    message: "Rename to `y`".into(),
+                    substitutions: vec![(SrcSpan::new(4, 5), "y".into())],
+                    applicability: Applicability::MachineApplicable,
+                },
+                // Not machine-applicable, so it should not produce a quick fix.
+                Suggestion {
+                    message: "Maybe rename to `z`".into(),
+                    substitutions: vec![(SrcSpan::new(4, 5), "z".into())],
+                    applicability: Applicability::MaybeIncorrect,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let actions = code_actions(&uri, &diagnostic);
+        assert_eq!(actions.len(), 1);
+
+        match &actions[0] {
+            CodeActionOrCommand::CodeAction(action) => {
+                assert_eq!(action.title, "Rename to `y`");
+                let changes = action
+                    .edit
+                    .as_ref()
+                    .expect("edit")
+                    .changes
+                    .as_ref()
+                    .expect("changes");
+                let edits = changes.get(&uri).expect("edits for file");
+                assert_eq!(edits.len(), 1);
+                assert_eq!(edits[0].new_text, "y");
+                assert_eq!(
+                    edits[0].range,
+                    Range {
+                        start: Position {
+                            line: 0,
+                            character: 4
+                        },
+                        end: Position {
+                            line: 0,
+                            character: 5
+                        },
+                    }
+                );
+            }
+            other => panic!("expected a CodeAction, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn machine_applicable_substitutions_carries_the_cached_source_snapshot() {
+        let path = PathBuf::from("/project/src/file1.gleam");
+        let src: EcoString = "let x = 1\n".into();
+        let diagnostic = Diagnostic {
+            title: "Unused variable".into(),
+            location: Some(Location {
+                path: path.clone(),
+                src: src.clone(),
+                label: Label {
+                    text: None,
+                    span: SrcSpan::new(4, 5),
+                },
+                extra_labels: vec![],
+            }),
+            suggestions: vec![
+                Suggestion {
+                    message: "Rename".into(),
+                    substitutions: vec![(SrcSpan::new(4, 5), "y".into())],
+                    applicability: Applicability::MachineApplicable,
+                },
+                Suggestion {
+                    message: "Not applied automatically".into(),
+                    substitutions: vec![(SrcSpan::new(4, 5), "z".into())],
+                    applicability: Applicability::MaybeIncorrect,
+                },
+            ],
+            ..Default::default()
+        };
+        // A diagnostic with no location has nowhere to apply its suggestions
+        // and so is skipped entirely.
+        let locationless = Diagnostic {
+            title: "Could not compress build artefacts".into(),
+            suggestions: vec![Suggestion {
+                message: "unused".into(),
+                substitutions: vec![(SrcSpan::new(0, 1), "_".into())],
+                applicability: Applicability::MachineApplicable,
+            }],
+            ..Default::default()
+        };
+
+        let mut feedback = Feedback::default();
+        _ = feedback.append_diagnostic(path.clone(), diagnostic);
+        _ = feedback.append_diagnostic(path.clone(), locationless);
+
+        assert_eq!(
+            feedback.machine_applicable_substitutions(),
+            HashMap::from([(path, (src, vec![(SrcSpan::new(4, 5), "y".to_string())]))])
+        );
+    }
+
+    #[test]
+    fn apply_machine_applicable_fixes_applies_against_the_cached_snapshot_not_disk() {
+        // The substitution spans were computed against `src` at diagnostic
+        // creation time. The file on disk has since drifted to something else
+        // entirely (e.g. another process wrote to it, or the cache is stale).
+        // Applying the fix must use the cached snapshot rather than whatever
+        // is on disk now, or this would panic trying to `replace_range` spans
+        // that no longer fit the file's current length.
+        let path = std::env::temp_dir().join(format!(
+            "gleam_feedback_test_apply_fix_{}.gleam",
+            std::process::id()
+        ));
+        let src: EcoString = "let wibble = 1\n".into();
+        std::fs::write(&path, "x").expect("write drifted fixture");
+
+        let diagnostic = Diagnostic {
+            location: Some(Location {
+                path: path.clone(),
+                src: src.clone(),
+                label: Label {
+                    text: None,
+                    span: SrcSpan::new(4, 10),
+                },
+                extra_labels: vec![],
+            }),
+            suggestions: vec![Suggestion {
+                message: "Rename to `x`".into(),
+                substitutions: vec![(SrcSpan::new(4, 10), "x".into())],
+                applicability: Applicability::MachineApplicable,
+            }],
+            ..Default::default()
+        };
+
+        let mut feedback = Feedback::default();
+        _ = feedback.append_diagnostic(path.clone(), diagnostic);
+
+        let edited = feedback
+            .apply_machine_applicable_fixes()
+            .expect("applying fixes should not fail");
+
+        assert_eq!(edited, vec![path.clone()]);
+        assert_eq!(
+            std::fs::read_to_string(&path).expect("read back"),
+            "let x = 1\n"
+        );
+
+        std::fs::remove_file(&path).expect("cleanup fixture");
+    }
+
+    #[test]
+    fn to_json_feedback_renders_diagnostics_and_messages_with_hints_as_help() {
+        let path = PathBuf::from("/project/src/file1.gleam");
+        let src: EcoString = "let x = 1\n".into();
+        let diagnostic = Diagnostic {
+            title: "Unused variable".into(),
+            level: Level::Warning,
+            location: Some(Location {
+                path: path.clone(),
+                src: src.clone(),
+                label: Label {
+                    text: None,
+                    span: SrcSpan::new(4, 5),
+                },
+                extra_labels: vec![],
+            }),
+            hint: Some("Prefix with `_` to ignore it".into()),
+            ..Default::default()
+        };
+        let message = Diagnostic {
+            title: "Could not compress build artefacts".into(),
+            level: Level::Error,
+            ..Default::default()
+        };
+
+        let mut feedback = Feedback::default();
+        _ = feedback.append_diagnostic(path.clone(), diagnostic);
+        feedback.append_message(message);
+
+        assert_eq!(
+            feedback.to_json_feedback(),
+            JsonFeedback {
+                diagnostics: HashMap::from([(
+                    path.to_string_lossy().to_string(),
+                    vec![
+                        JsonDiagnostic {
+                            level: "warning",
+                            message: "Unused variable".into(),
+                            snippet: Some("x".into()),
+                            spans: vec![JsonSpan {
+                                byte_start: 4,
+                                byte_end: 5,
+                                line_start: 1,
+                                column_start: 5,
+                                line_end: 1,
+                                column_end: 6,
+                            }],
+                        },
+                        JsonDiagnostic {
+                            level: "help",
+                            message: "Prefix with `_` to ignore it".into(),
+                            snippet: None,
+                            spans: vec![],
+                        },
+                    ]
+                )]),
+                messages: vec![JsonDiagnostic {
+                    level: "error",
+                    message: "Could not compress build artefacts".into(),
+                    snippet: None,
+                    spans: vec![],
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn related_information_resolves_child_spans_from_the_cached_snapshot() {
+        let diagnostic = Diagnostic {
+            children: vec![RelatedDiagnostic {
+                path: PathBuf::from("/project/src/other.gleam"),
+                src: "one\ntwo\nthree\n".into(),
+                span: SrcSpan::new(4, 7),
+                message: "defined here".into(),
+            }],
+            ..Default::default()
+        };
+
+        let related = related_information(&diagnostic);
+
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].message, "defined here");
+        assert_eq!(
+            related[0].location.uri,
+            Url::from_file_path("/project/src/other.gleam").expect("absolute path")
+        );
+        assert_eq!(
+            related[0].location.range,
+            Range {
+                start: Position {
+                    line: 1,
+                    character: 0
+                },
+                end: Position {
+                    line: 1,
+                    character: 3
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn related_information_drops_children_with_a_non_absolute_path() {
+        // `Url::from_file_path` only accepts absolute paths, so a child whose
+        // path cannot be turned into a file URI is silently skipped rather
+        // than panicking.
+        let diagnostic = Diagnostic {
+            children: vec![RelatedDiagnostic {
+                path: PathBuf::from("src/other.gleam"),
+                src: "one\n".into(),
+                span: SrcSpan::new(0, 1),
+                message: "defined here".into(),
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(related_information(&diagnostic), vec![]);
+    }
 }